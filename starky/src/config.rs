@@ -0,0 +1,41 @@
+//! Configuration shared by the STARK prover and verifier: how many Fiat-Shamir challenges to
+//! draw for soundness-amplifying arguments (permutation/lookup/CTL), and the FRI parameters to
+//! run underneath.
+
+use plonky2::fri::reduction_strategies::FriReductionStrategy;
+use plonky2::fri::{FriConfig, FriParams};
+
+#[derive(Clone, Debug)]
+pub struct StarkConfig {
+    /// Targeted security level, in bits.
+    pub security_bits: usize,
+    /// The number of challenge points to sample for each soundness-amplifying argument (the
+    /// permutation argument, cross-table lookups, ...). The AIR constraints are combined
+    /// `num_challenges` times with independent `alpha`s. The permutation argument draws
+    /// `num_challenges` `(beta, gamma)` pairs per `Z` polynomial, but the `j`-th column pair of a
+    /// batch uses only pair `j % num_challenges`; its constraints are not repeated per challenge.
+    pub num_challenges: usize,
+    pub fri_config: FriConfig,
+}
+
+impl StarkConfig {
+    /// A reasonable default, trading proof size for faster proving; matches the config used
+    /// elsewhere in plonky2 for STARKs.
+    pub fn standard_fast_config() -> Self {
+        Self {
+            security_bits: 100,
+            num_challenges: 2,
+            fri_config: FriConfig {
+                rate_bits: 1,
+                cap_height: 4,
+                proof_of_work_bits: 16,
+                reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+                num_query_rounds: 84,
+            },
+        }
+    }
+
+    pub(crate) fn fri_params(&self, degree_bits: usize) -> FriParams {
+        self.fri_config.fri_params(degree_bits, false)
+    }
+}
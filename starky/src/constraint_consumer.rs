@@ -0,0 +1,159 @@
+//! Accumulates a STARK's constraint evaluations into `num_challenges` independent random linear
+//! combinations (the "alpha" batching used throughout plonky2), with boundary-constraint helpers
+//! for the first/last row of the trace. [`ConstraintConsumer`] is the native version; its openings
+//! are the operand type `P`. [`RecursiveConstraintConsumer`] is its in-circuit mirror.
+
+use plonky2::field::extension_field::target::ExtensionTarget;
+use plonky2::field::extension_field::Extendable;
+use plonky2::field::packed_field::PackedField;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::target::Target;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+
+pub struct ConstraintConsumer<P: PackedField> {
+    /// Random combination coefficients, one per challenge.
+    alphas: Vec<P::Scalar>,
+    /// The running accumulator for each challenge in `alphas`.
+    constraint_accs: Vec<P>,
+    /// 1 at the first row of the trace, 0 elsewhere.
+    lagrange_basis_first: P,
+    /// 1 at the last row of the trace, 0 elsewhere.
+    lagrange_basis_last: P,
+}
+
+impl<P: PackedField> ConstraintConsumer<P> {
+    pub fn new(alphas: Vec<P::Scalar>, lagrange_basis_first: P, lagrange_basis_last: P) -> Self {
+        Self {
+            constraint_accs: vec![P::ZEROS; alphas.len()],
+            alphas,
+            lagrange_basis_first,
+            lagrange_basis_last,
+        }
+    }
+
+    pub fn accumulators(self) -> Vec<P> {
+        self.constraint_accs
+    }
+
+    /// Add a constraint that must vanish at every row but the last (the verifier divides the
+    /// combined constraints by `x - g^{n-1}`, so the last row is left unconstrained).
+    pub fn constraint(&mut self, constraint: P) {
+        for (&alpha, acc) in self.alphas.iter().zip(&mut self.constraint_accs) {
+            *acc = *acc * alpha.into() + constraint;
+        }
+    }
+
+    /// A constraint that should vanish at every row but the last.
+    pub fn constraint_transition(&mut self, constraint: P) {
+        self.constraint(constraint);
+    }
+
+    /// A constraint that applies only at the first row: it is multiplied by `L_1(x)`, so it must
+    /// vanish at the first row and is unconstrained elsewhere.
+    pub fn constraint_first_row(&mut self, constraint: P) {
+        self.constraint(constraint * self.lagrange_basis_first);
+    }
+
+    /// A constraint gated to the last row: it is multiplied by `L_last(x)`, which is zero at
+    /// every other row.
+    pub fn constraint_last_row(&mut self, constraint: P) {
+        self.constraint(constraint * self.lagrange_basis_last);
+    }
+}
+
+/// In-circuit mirror of [`ConstraintConsumer`]: every field element becomes an `ExtensionTarget`,
+/// and folding a constraint in requires `builder` since the accumulator lives in the circuit.
+pub struct RecursiveConstraintConsumer<F: RichField + Extendable<D>, const D: usize> {
+    zero: ExtensionTarget<D>,
+    alphas: Vec<Target>,
+    lagrange_basis_first: ExtensionTarget<D>,
+    lagrange_basis_last: ExtensionTarget<D>,
+    constraint_accs: Vec<ExtensionTarget<D>>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> RecursiveConstraintConsumer<F, D> {
+    pub fn new(
+        zero: ExtensionTarget<D>,
+        alphas: Vec<Target>,
+        lagrange_basis_first: ExtensionTarget<D>,
+        lagrange_basis_last: ExtensionTarget<D>,
+    ) -> Self {
+        Self {
+            zero,
+            constraint_accs: vec![zero; alphas.len()],
+            alphas,
+            lagrange_basis_first,
+            lagrange_basis_last,
+        }
+    }
+
+    pub fn accumulators(&self) -> Vec<ExtensionTarget<D>> {
+        self.constraint_accs.clone()
+    }
+
+    pub fn constraint(&mut self, builder: &mut CircuitBuilder<F, D>, constraint: ExtensionTarget<D>) {
+        for (&alpha, acc) in self.alphas.iter().zip(&mut self.constraint_accs) {
+            let alpha_ext = builder.convert_to_ext(alpha);
+            let scaled = builder.mul_extension(alpha_ext, *acc);
+            *acc = builder.add_extension(scaled, constraint);
+        }
+    }
+
+    pub fn constraint_transition(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        constraint: ExtensionTarget<D>,
+    ) {
+        self.constraint(builder, constraint);
+    }
+
+    pub fn constraint_first_row(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        constraint: ExtensionTarget<D>,
+    ) {
+        let scaled = builder.mul_extension(constraint, self.lagrange_basis_first);
+        self.constraint(builder, scaled);
+    }
+
+    pub fn constraint_last_row(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        constraint: ExtensionTarget<D>,
+    ) {
+        let scaled = builder.mul_extension(constraint, self.lagrange_basis_last);
+        self.constraint(builder, scaled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::field_types::Field;
+    use plonky2::field::goldilocks_field::GoldilocksField as F;
+
+    use super::ConstraintConsumer;
+
+    #[test]
+    fn accumulates_one_challenge_per_alpha() {
+        let alphas = vec![F::TWO, F::from_canonical_u64(5)];
+        let mut consumer = ConstraintConsumer::<F>::new(alphas.clone(), F::ONE, F::ZERO);
+        consumer.constraint(F::ONE);
+        consumer.constraint(F::from_canonical_u64(3));
+        let acc = consumer.accumulators();
+        // acc[i] = ((0 * alpha_i + 1) * alpha_i + 3)
+        for (i, &alpha) in alphas.iter().enumerate() {
+            assert_eq!(acc[i], alpha + F::from_canonical_u64(3));
+        }
+    }
+
+    #[test]
+    fn first_and_last_row_constraints_are_gated() {
+        let mut consumer = ConstraintConsumer::<F>::new(vec![F::TWO], F::ONE, F::ZERO);
+        // A first-row constraint with lagrange_basis_first = 1 passes through unscaled.
+        consumer.constraint_first_row(F::from_canonical_u64(7));
+        // A last-row constraint with lagrange_basis_last = 0 contributes nothing.
+        consumer.constraint_last_row(F::from_canonical_u64(1000));
+        let acc = consumer.accumulators();
+        assert_eq!(acc[0], F::from_canonical_u64(7));
+    }
+}
@@ -0,0 +1,357 @@
+//! Verification of cross-table lookups (CTLs): an auxiliary running-sum argument tying together
+//! several STARK tables proved together, e.g. an arithmetic table's outputs "looked up" by a
+//! logic table's inputs.
+
+use anyhow::{ensure, Result};
+use plonky2::field::extension_field::{Extendable, FieldExtension};
+use plonky2::field::field_types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::iop::challenger::Challenger;
+use plonky2::plonk::config::{GenericConfig, Hasher};
+
+use crate::config::StarkConfig;
+use crate::constraint_consumer::ConstraintConsumer;
+use crate::permutation::PermutationChallenge;
+use crate::proof::StarkProofWithPublicInputs;
+
+/// One table's columns participating in a lookup, either as a "looking" side or the single
+/// "looked" side.
+#[derive(Debug, Clone)]
+pub struct TableWithColumns {
+    pub table_index: usize,
+    pub columns: Vec<usize>,
+    /// An optional 0/1 filter column restricting which rows of `table_index` participate.
+    pub filter_column: Option<usize>,
+}
+
+/// A single cross-table lookup: every row looked up by any of the `looking` tables must appear,
+/// with matching multiplicity, among the rows of the `looked` table.
+#[derive(Debug, Clone)]
+pub struct CrossTableLookup {
+    pub looking: Vec<TableWithColumns>,
+    pub looked: TableWithColumns,
+}
+
+/// The `(beta, gamma)` pair shared by every table's CTL-Z polynomials, squeezed from a single
+/// challenger that has observed every table's trace cap.
+pub fn get_ctl_challenge<F: RichField, H: plonky2::hash::hashing::PlonkyPermutation>(
+    challenger: &mut Challenger<F, H>,
+) -> PermutationChallenge<F> {
+    PermutationChallenge {
+        beta: challenger.get_challenge(),
+        gamma: challenger.get_challenge(),
+    }
+}
+
+/// Per-table auxiliary data needed to fold a CTL's boundary/transition constraints into that
+/// table's `ConstraintConsumer`.
+pub struct CtlCheckVars<F, FE, P, const D2: usize>
+where
+    F: Field,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: plonky2::field::packed_field::PackedField<Scalar = FE>,
+{
+    pub local_z: P,
+    pub next_z: P,
+    pub challenges: PermutationChallenge<F>,
+    pub columns: Vec<usize>,
+    pub filter_column: Option<usize>,
+    /// `Z`'s final (last-row) value, i.e. the table's contribution to the lookup's total.
+    pub local_z_last: FE,
+}
+
+/// Fold a single table's CTL-Z boundary and transition constraints into `consumer`:
+/// * boundary: `L_last(x) * (Z(x) - z_last) = 0`
+/// * transition: `Z(g x) - Z(x) - filter(x) / (gamma + sum_j beta^j col_j(x)) = 0`
+pub fn eval_cross_table_lookup_checks<F, FE, P, const D2: usize, const COLUMNS: usize, const PUBLIC_INPUTS: usize>(
+    vars: &crate::vars::StarkEvaluationVars<FE, P, COLUMNS, PUBLIC_INPUTS>,
+    ctl_vars: &CtlCheckVars<F, FE, P, D2>,
+    consumer: &mut ConstraintConsumer<P>,
+) where
+    F: Field,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: plonky2::field::packed_field::PackedField<Scalar = FE>,
+{
+    let CtlCheckVars {
+        local_z,
+        next_z,
+        challenges,
+        columns,
+        filter_column,
+        local_z_last,
+    } = ctl_vars;
+
+    let beta = FE::from_basefield(challenges.beta);
+    let gamma = FE::from_basefield(challenges.gamma);
+
+    consumer.constraint_last_row(*local_z - (*local_z_last).into());
+
+    let filter = filter_column
+        .map(|col| vars.local_values[col])
+        .unwrap_or(P::ONES);
+    let mut combined = gamma.into();
+    for (j, &col) in columns.iter().enumerate() {
+        combined += beta.exp_u64(j as u64).into() * vars.local_values[col];
+    }
+    // `Z(g x) - Z(x) = filter(x) / combined(x)`, cleared of the denominator:
+    consumer.constraint_transition((*next_z - *local_z) * combined - filter);
+}
+
+/// The number of times each table appears (as a looking or looked side) across
+/// `cross_table_lookups`, which is also how many CTL-Z final values `verify_table` must return for
+/// that table, in the order `verify_cross_table_lookups` consumes them in.
+fn participation_counts(cross_table_lookups: &[CrossTableLookup], num_tables: usize) -> Vec<usize> {
+    let mut counts = vec![0; num_tables];
+    for ctl in cross_table_lookups {
+        for twc in ctl.looking.iter().chain(std::iter::once(&ctl.looked)) {
+            counts[twc.table_index] += 1;
+        }
+    }
+    counts
+}
+
+/// Verify every [`CrossTableLookup`] against a collection of already-per-table-verified proofs:
+/// for each lookup, the sum of the looking tables' final CTL-Z values must equal the looked
+/// table's final CTL-Z value. A table flagged unused in `is_used` is required to contribute zero.
+///
+/// `ctl_zs_last[table_index]` holds one final value per lookup that table participates in (as
+/// either a looking or the looked side), in the order `cross_table_lookups` is iterated below —
+/// a table that is both a looking table for one lookup and the looked table for another needs two
+/// distinct entries here, not one shared scalar.
+pub fn verify_cross_table_lookups<F: Field>(
+    cross_table_lookups: &[CrossTableLookup],
+    ctl_zs_last: &[Vec<F>],
+    is_used: &[bool],
+) -> Result<()> {
+    let mut next_slot = vec![0usize; ctl_zs_last.len()];
+
+    for ctl in cross_table_lookups {
+        let looking_sum: F = ctl
+            .looking
+            .iter()
+            .map(|twc| {
+                let slot = next_slot[twc.table_index];
+                next_slot[twc.table_index] += 1;
+                ctl_zs_last[twc.table_index][slot]
+            })
+            .sum();
+
+        let looked_slot = next_slot[ctl.looked.table_index];
+        next_slot[ctl.looked.table_index] += 1;
+        let looked_sum = ctl_zs_last[ctl.looked.table_index][looked_slot];
+
+        ensure!(
+            looking_sum == looked_sum,
+            "cross-table lookup final sums do not match"
+        );
+    }
+
+    for (table_index, used) in is_used.iter().enumerate() {
+        if !*used {
+            for &z_last in &ctl_zs_last[table_index] {
+                ensure!(
+                    z_last == F::ZERO,
+                    "an unused table must contribute zero to every cross-table lookup"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Verify `N` STARK tables tied together by `cross_table_lookups`. `is_used[i]` says whether
+/// table `i` was actually executed; an unused table's FRI/quotient check is skipped entirely, but
+/// its trace cap's slot in the transcript is still filled with a canonical placeholder (an
+/// all-zero Merkle cap of the same shape), and its CTL-Z final values are taken to be zero. This
+/// lets a single fixed recursive wrapper circuit verify executions with varying table sets.
+///
+/// Every table's trace Merkle cap (or its placeholder) is observed by a single [`Challenger`], in
+/// order, before the shared CTL challenges `(beta, gamma)` are squeezed. `verify_table` should
+/// verify one used table's quotient/FRI check as usual (folding in its `CtlCheckVars`, built from
+/// `ctl_challenges`) and return that table's final CTL-Z value per lookup it participates in.
+pub fn verify_multi<F, C, const D: usize, const N: usize>(
+    proofs_with_pis: [Option<StarkProofWithPublicInputs<F, C, D>>; N],
+    is_used: [bool; N],
+    cross_table_lookups: &[CrossTableLookup],
+    config: &StarkConfig,
+    verify_table: impl Fn(
+        usize,
+        &StarkProofWithPublicInputs<F, C, D>,
+        &[PermutationChallenge<F>],
+    ) -> Result<Vec<F>>,
+) -> Result<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    for (used, proof) in is_used.iter().zip(&proofs_with_pis) {
+        ensure!(
+            *used == proof.is_some(),
+            "a table's proof must be present if and only if it is flagged used"
+        );
+    }
+
+    // A real cap is `1 << cap_height` sibling hashes; `MerkleCap::default()` is an *empty* vector,
+    // which would make the challenger absorb a different number of elements for an absent table
+    // than for a present one, changing every challenge squeezed afterwards. Build a same-shape
+    // all-zero cap instead so the transcript is identical whether or not the unused table's proof
+    // is present.
+    let placeholder_cap = MerkleCap::<F, C::Hasher>(vec![
+        <C::Hasher as Hasher<F>>::Hash::default();
+        1 << config.fri_config.cap_height
+    ]);
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    for proof_with_pis in &proofs_with_pis {
+        match proof_with_pis {
+            Some(proof_with_pis) => challenger.observe_cap(&proof_with_pis.proof.trace_cap),
+            None => challenger.observe_cap(&placeholder_cap),
+        }
+    }
+    let ctl_challenges = (0..config.num_challenges)
+        .map(|_| get_ctl_challenge(&mut challenger))
+        .collect::<Vec<_>>();
+
+    let participation_counts = participation_counts(cross_table_lookups, N);
+    let mut ctl_zs_last = Vec::with_capacity(N);
+    for (i, proof_with_pis) in proofs_with_pis.iter().enumerate() {
+        ctl_zs_last.push(match proof_with_pis {
+            Some(proof_with_pis) => verify_table(i, proof_with_pis, &ctl_challenges)?,
+            None => vec![F::ZERO; participation_counts[i]],
+        });
+    }
+
+    verify_cross_table_lookups(cross_table_lookups, &ctl_zs_last, &is_used)
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::field_types::Field;
+    use plonky2::field::goldilocks_field::GoldilocksField as F;
+
+    use super::*;
+
+    fn twc(table_index: usize) -> TableWithColumns {
+        TableWithColumns {
+            table_index,
+            columns: vec![0],
+            filter_column: None,
+        }
+    }
+
+    #[test]
+    fn placeholder_cap_has_real_cap_shape() {
+        use plonky2::field::goldilocks_field::GoldilocksField;
+        use plonky2::plonk::config::{GenericConfig, Hasher, PoseidonGoldilocksConfig};
+
+        type C = PoseidonGoldilocksConfig;
+        let cap_height = 3;
+        let placeholder_cap = MerkleCap::<GoldilocksField, <C as GenericConfig<2>>::Hasher>(vec![
+            <<C as GenericConfig<2>>::Hasher as Hasher<GoldilocksField>>::Hash::default();
+            1 << cap_height
+        ]);
+        // `MerkleCap::default()` would instead have zero siblings, absorbing a different number of
+        // elements into the challenger than a real table's cap of this height does.
+        assert_eq!(placeholder_cap.0.len(), 1 << cap_height);
+    }
+
+    #[test]
+    fn table_used_in_two_lookups_gets_two_distinct_slots() {
+        // Table 1 is the looked side of lookup A and a looking side of lookup B: it must be able
+        // to report two different final values, not have one scalar checked against both sums.
+        let cross_table_lookups = vec![
+            CrossTableLookup {
+                looking: vec![twc(0)],
+                looked: twc(1),
+            },
+            CrossTableLookup {
+                looking: vec![twc(1), twc(2)],
+                looked: twc(3),
+            },
+        ];
+        let ctl_zs_last = vec![
+            vec![F::from_canonical_u64(5)],                             // table 0: lookup A looking
+            vec![F::from_canonical_u64(5), F::from_canonical_u64(2)],   // table 1: A looked, B looking
+            vec![F::from_canonical_u64(1)],                             // table 2: B looking
+            vec![F::from_canonical_u64(3)],                             // table 3: B looked
+        ];
+        let is_used = vec![true; 4];
+
+        assert!(verify_cross_table_lookups(&cross_table_lookups, &ctl_zs_last, &is_used).is_ok());
+    }
+
+    #[test]
+    fn mismatched_sum_is_rejected() {
+        let cross_table_lookups = vec![CrossTableLookup {
+            looking: vec![twc(0)],
+            looked: twc(1),
+        }];
+        let ctl_zs_last = vec![vec![F::from_canonical_u64(5)], vec![F::from_canonical_u64(6)]];
+        let is_used = vec![true; 2];
+
+        assert!(verify_cross_table_lookups(&cross_table_lookups, &ctl_zs_last, &is_used).is_err());
+    }
+
+    #[test]
+    fn unused_table_must_contribute_zero() {
+        let cross_table_lookups = vec![CrossTableLookup {
+            looking: vec![twc(0)],
+            looked: twc(1),
+        }];
+        let ctl_zs_last = vec![vec![F::from_canonical_u64(5)], vec![F::from_canonical_u64(5)]];
+        let is_used = vec![false, true];
+
+        assert!(verify_cross_table_lookups(&cross_table_lookups, &ctl_zs_last, &is_used).is_err());
+    }
+
+    #[test]
+    fn participation_counts_count_every_role() {
+        let cross_table_lookups = vec![
+            CrossTableLookup {
+                looking: vec![twc(0)],
+                looked: twc(1),
+            },
+            CrossTableLookup {
+                looking: vec![twc(1), twc(2)],
+                looked: twc(3),
+            },
+        ];
+        assert_eq!(participation_counts(&cross_table_lookups, 4), vec![1, 2, 1, 1]);
+    }
+
+    #[test]
+    fn eval_cross_table_lookup_checks_enforces_running_sum_step() {
+        let beta = F::from_canonical_u64(7);
+        let gamma = F::from_canonical_u64(11);
+        let col0_local = F::from_canonical_u64(3);
+        let filter = F::ONE;
+
+        // The only column has index `j = 0`, so its coefficient is `beta^0 = 1`.
+        let combined = gamma + col0_local;
+        let local_z = F::from_canonical_u64(2);
+        // Satisfy `(next_z - local_z) * combined = filter` exactly.
+        let next_z = local_z + filter * combined.inverse();
+
+        let vars = crate::vars::StarkEvaluationVars {
+            local_values: &[col0_local],
+            next_values: &[F::ZERO],
+            preprocessed_values: &[],
+            public_inputs: &[],
+        };
+        let ctl_vars = CtlCheckVars::<F, F, F, 1> {
+            local_z,
+            next_z,
+            challenges: PermutationChallenge { beta, gamma },
+            columns: vec![0],
+            filter_column: None,
+            local_z_last: local_z,
+        };
+        let mut consumer = ConstraintConsumer::<F>::new(vec![F::ONE], F::ZERO, F::ONE);
+        eval_cross_table_lookup_checks::<F, F, F, 1, 1, 0>(&vars, &ctl_vars, &mut consumer);
+
+        // Only the (gated) boundary constraint fires, since `lagrange_basis_last = 1` here and the
+        // transition constraint we set up above evaluates to zero.
+        let acc = consumer.accumulators();
+        assert_eq!(acc[0], F::ZERO);
+    }
+}
@@ -0,0 +1,345 @@
+//! Utilities for the permutation/lookup argument: given a `Stark`'s declared column pairings,
+//! compute and verify the running-product polynomials `Z` that prove a multiset equality between
+//! two sets of columns.
+
+use plonky2::field::extension_field::target::ExtensionTarget;
+use plonky2::field::extension_field::{Extendable, FieldExtension};
+use plonky2::field::field_types::Field;
+use plonky2::hash::hash_types::RichField;
+use plonky2::iop::challenger::Challenger;
+use plonky2::iop::target::Target;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::config::GenericConfig;
+
+use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+
+/// A pair of columns which should be permutations of one another, modulo the trailing `i`-th
+/// "index" term added to each entry (so that the argument also proves a fixed bijection rather
+/// than an arbitrary one, when needed).
+#[derive(Debug, Clone)]
+pub struct PermutationPair {
+    pub column_pairs: Vec<(usize, usize)>,
+}
+
+impl PermutationPair {
+    pub fn singletons(lhs: usize, rhs: usize) -> Self {
+        Self {
+            column_pairs: vec![(lhs, rhs)],
+        }
+    }
+}
+
+/// The `(beta, gamma)` challenges used to batch a [`PermutationPair`]'s columns into a single
+/// running-product check.
+#[derive(Debug, Copy, Clone)]
+pub struct PermutationChallenge<F: Field> {
+    pub beta: F,
+    pub gamma: F,
+}
+
+/// A set of permutation challenges, one per `Z` polynomial committed by the prover.
+#[derive(Debug, Clone)]
+pub struct PermutationChallengeSet<F: Field> {
+    pub challenges: Vec<PermutationChallenge<F>>,
+}
+
+/// Squeeze one `(beta, gamma)` pair from the challenger.
+pub fn get_permutation_challenge<F: RichField, H: plonky2::hash::hashing::PlonkyPermutation>(
+    challenger: &mut Challenger<F, H>,
+) -> PermutationChallenge<F> {
+    let beta = challenger.get_challenge();
+    let gamma = challenger.get_challenge();
+    PermutationChallenge { beta, gamma }
+}
+
+pub fn get_n_permutation_challenge_sets<
+    F: RichField,
+    H: plonky2::hash::hashing::PlonkyPermutation,
+>(
+    challenger: &mut Challenger<F, H>,
+    num_challenges: usize,
+    num_sets: usize,
+) -> Vec<PermutationChallengeSet<F>> {
+    (0..num_sets)
+        .map(|_| PermutationChallengeSet {
+            challenges: (0..num_challenges)
+                .map(|_| get_permutation_challenge(challenger))
+                .collect(),
+        })
+        .collect()
+}
+
+/// In-circuit mirror of [`PermutationChallenge`].
+#[derive(Debug, Copy, Clone)]
+pub struct PermutationChallengeTarget {
+    pub beta: Target,
+    pub gamma: Target,
+}
+
+/// In-circuit mirror of [`PermutationChallengeSet`].
+#[derive(Debug, Clone)]
+pub struct PermutationChallengeSetTarget {
+    pub challenges: Vec<PermutationChallengeTarget>,
+}
+
+/// The data needed to evaluate the permutation argument's boundary and transition constraints at
+/// a point, for every `Z` polynomial of a `Stark`.
+pub struct PermutationCheckVars<F, FE, P, const D2: usize>
+where
+    F: Field,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: plonky2::field::packed_field::PackedField<Scalar = FE>,
+{
+    pub local_zs: Vec<P>,
+    pub next_zs: Vec<P>,
+    pub permutation_challenge_sets: Vec<PermutationChallengeSet<F>>,
+}
+
+/// Fold the permutation argument's boundary and transition constraints into `consumer`, sharing
+/// its `Z_H(zeta)/z_last` vanishing check with the AIR constraints.
+///
+/// For each pair's running-product polynomial `Z`:
+/// * boundary: `L_1(x) * (Z(x) - 1) = 0`
+/// * transition: `Z(g x) * prod(rhs_i(x) + beta*i + gamma) - Z(x) * prod(lhs_i(x) + beta*i + gamma) = 0`,
+///   where `lhs_i`/`rhs_i` are the two columns of the `i`-th pair in the batch
+pub fn eval_permutation_checks<F, FE, P, S, const D: usize, const D2: usize>(
+    stark: &S,
+    config: &crate::config::StarkConfig,
+    vars: crate::vars::StarkEvaluationVars<FE, P, { S::COLUMNS }, { S::PUBLIC_INPUTS }>,
+    permutation_vars: PermutationCheckVars<F, FE, P, D2>,
+    consumer: &mut ConstraintConsumer<P>,
+) where
+    F: RichField + Extendable<D>,
+    FE: FieldExtension<D2, BaseField = F>,
+    P: plonky2::field::packed_field::PackedField<Scalar = FE>,
+    S: crate::stark::Stark<F, D>,
+    [(); S::COLUMNS]:,
+    [(); S::PUBLIC_INPUTS]:,
+{
+    let PermutationCheckVars {
+        local_zs,
+        next_zs,
+        permutation_challenge_sets,
+    } = permutation_vars;
+
+    let permutation_pairs = stark.permutation_pairs();
+    let permutation_batch_size = stark.permutation_batch_size();
+
+    for (i, chunk) in permutation_pairs.chunks(permutation_batch_size).enumerate() {
+        let z_local = local_zs[i];
+        let z_next = next_zs[i];
+
+        // Boundary constraint: Z(first row) = 1.
+        consumer.constraint_first_row(z_local - P::ONES);
+
+        let mut numerator = P::ONES;
+        let mut denominator = P::ONES;
+        for (j, pair) in chunk.iter().enumerate() {
+            let challenge = &permutation_challenge_sets[i].challenges[j % config.num_challenges];
+            let beta = FE::from_basefield(challenge.beta);
+            let gamma = FE::from_basefield(challenge.gamma);
+            for &(lhs, rhs) in &pair.column_pairs {
+                numerator *= vars.local_values[lhs] + beta.into() * FE::from_canonical_usize(j).into() + gamma.into();
+                denominator *= vars.local_values[rhs] + beta.into() * FE::from_canonical_usize(j).into() + gamma.into();
+            }
+        }
+
+        // Transition constraint: Z(g x) * denominator - Z(x) * numerator = 0.
+        consumer.constraint_transition(z_next * denominator - z_local * numerator);
+    }
+}
+
+/// In-circuit mirror of [`PermutationCheckVars`].
+pub struct PermutationCheckVarsTarget<const D: usize> {
+    pub local_zs: Vec<ExtensionTarget<D>>,
+    pub next_zs: Vec<ExtensionTarget<D>>,
+    pub permutation_challenge_sets: Vec<PermutationChallengeSetTarget>,
+}
+
+/// In-circuit mirror of [`eval_permutation_checks`].
+pub fn eval_permutation_checks_circuit<F, S, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: &S,
+    config: &crate::config::StarkConfig,
+    vars: crate::vars::StarkEvaluationTargets<D, { S::COLUMNS }, { S::PUBLIC_INPUTS }>,
+    permutation_vars: PermutationCheckVarsTarget<D>,
+    consumer: &mut RecursiveConstraintConsumer<F, D>,
+) where
+    F: RichField + Extendable<D>,
+    S: crate::stark::Stark<F, D>,
+    [(); S::COLUMNS]:,
+    [(); S::PUBLIC_INPUTS]:,
+{
+    let PermutationCheckVarsTarget {
+        local_zs,
+        next_zs,
+        permutation_challenge_sets,
+    } = permutation_vars;
+
+    let permutation_pairs = stark.permutation_pairs();
+    let permutation_batch_size = stark.permutation_batch_size();
+
+    let one = builder.one_extension();
+    for (i, chunk) in permutation_pairs.chunks(permutation_batch_size).enumerate() {
+        let z_local = local_zs[i];
+        let z_next = next_zs[i];
+
+        // Boundary constraint: Z(first row) = 1.
+        let z_minus_one = builder.sub_extension(z_local, one);
+        consumer.constraint_first_row(builder, z_minus_one);
+
+        let mut numerator = one;
+        let mut denominator = one;
+        for (j, pair) in chunk.iter().enumerate() {
+            let challenge = &permutation_challenge_sets[i].challenges[j % config.num_challenges];
+            let beta_ext = builder.convert_to_ext(challenge.beta);
+            let gamma_ext = builder.convert_to_ext(challenge.gamma);
+            let j_ext = builder.constant_extension(F::Extension::from_canonical_usize(j));
+            let beta_j = builder.mul_extension(beta_ext, j_ext);
+            let offset = builder.add_extension(beta_j, gamma_ext);
+            for &(lhs, rhs) in &pair.column_pairs {
+                let num_term = builder.add_extension(vars.local_values[lhs], offset);
+                numerator = builder.mul_extension(numerator, num_term);
+                let den_term = builder.add_extension(vars.local_values[rhs], offset);
+                denominator = builder.mul_extension(denominator, den_term);
+            }
+        }
+
+        // Transition constraint: Z(g x) * denominator - Z(x) * numerator = 0.
+        let lhs = builder.mul_extension(z_next, denominator);
+        let rhs = builder.mul_extension(z_local, numerator);
+        let transition = builder.sub_extension(lhs, rhs);
+        consumer.constraint_transition(builder, transition);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::extension_field::Extendable;
+    use plonky2::field::field_types::Field;
+    use plonky2::field::goldilocks_field::GoldilocksField as F;
+    use plonky2::field::packed_field::PackedField;
+
+    use super::*;
+    use crate::config::StarkConfig;
+    use crate::constraint_consumer::ConstraintConsumer;
+    use crate::stark::Stark;
+    use crate::vars::StarkEvaluationVars;
+
+    const D: usize = 2;
+    type FE = <F as Extendable<D>>::Extension;
+
+    /// A two-column `Stark` whose only argument is "column 0 is a permutation of column 1".
+    struct PairStark;
+
+    impl Stark<F, D> for PairStark {
+        const COLUMNS: usize = 2;
+        const PUBLIC_INPUTS: usize = 0;
+
+        fn eval_packed_generic<FE2, P, const D2: usize>(
+            &self,
+            _vars: StarkEvaluationVars<FE2, P, { Self::COLUMNS }, { Self::PUBLIC_INPUTS }>,
+            _consumer: &mut ConstraintConsumer<P>,
+        ) where
+            FE2: plonky2::field::extension_field::FieldExtension<D2, BaseField = F>,
+            P: PackedField<Scalar = FE2>,
+        {
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            _builder: &mut plonky2::plonk::circuit_builder::CircuitBuilder<F, D>,
+            _vars: crate::vars::StarkEvaluationTargets<D, { Self::COLUMNS }, { Self::PUBLIC_INPUTS }>,
+            _consumer: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+        }
+
+        fn constraint_degree(&self) -> usize {
+            2
+        }
+
+        fn permutation_pairs(&self) -> Vec<PermutationPair> {
+            vec![PermutationPair::singletons(0, 1)]
+        }
+
+        fn fri_instance(
+            _zeta: FE,
+            _g: F,
+            _rate_bits: usize,
+            _num_challenges: usize,
+        ) -> plonky2::fri::structure::FriInstanceInfo<F, D> {
+            unimplemented!("not exercised by these tests")
+        }
+
+        fn fri_instance_target(
+            _builder: &mut plonky2::plonk::circuit_builder::CircuitBuilder<F, D>,
+            _zeta: plonky2::field::extension_field::target::ExtensionTarget<D>,
+            _g: F,
+            _rate_bits: usize,
+            _num_challenges: usize,
+        ) -> plonky2::fri::structure::FriInstanceInfoTarget<D> {
+            unimplemented!("not exercised by these tests")
+        }
+    }
+
+    /// Isolate the transition constraint: alpha = 1 and both Lagrange bases are zero, so the
+    /// returned accumulator equals the transition term exactly (the boundary term is gated off,
+    /// and folding `0 * alpha + transition` leaves `transition` itself).
+    fn transition_term(local: [FE; 2], z_local: FE, z_next: FE, beta: F, gamma: F) -> FE {
+        let config = StarkConfig::standard_fast_config();
+        let vars = StarkEvaluationVars {
+            local_values: &local,
+            next_values: &local,
+            preprocessed_values: &[],
+            public_inputs: &[],
+        };
+        let permutation_vars = PermutationCheckVars {
+            local_zs: vec![z_local],
+            next_zs: vec![z_next],
+            permutation_challenge_sets: vec![PermutationChallengeSet {
+                challenges: vec![PermutationChallenge { beta, gamma }],
+            }],
+        };
+        let mut consumer =
+            ConstraintConsumer::<FE>::new(vec![FE::from_basefield(F::ONE)], FE::ZERO, FE::ZERO);
+        eval_permutation_checks::<F, FE, FE, PairStark, D, D>(
+            &PairStark,
+            &config,
+            vars,
+            permutation_vars,
+            &mut consumer,
+        );
+        consumer.accumulators()[0]
+    }
+
+    #[test]
+    fn satisfying_running_product_leaves_transition_constraint_zero() {
+        let beta = F::from_canonical_u64(7);
+        let gamma = F::from_canonical_u64(11);
+        let local = [FE::from_canonical_u64(3), FE::from_canonical_u64(5)];
+        let z_local = FE::from_canonical_u64(2);
+        // z_next * (local[1] + gamma) = z_local * (local[0] + gamma)
+        let numerator = local[0] + FE::from_basefield(gamma);
+        let denominator = local[1] + FE::from_basefield(gamma);
+        let z_next = z_local * numerator / denominator;
+
+        assert_eq!(
+            transition_term(local, z_local, z_next, beta, gamma),
+            FE::ZERO
+        );
+    }
+
+    #[test]
+    fn tampered_running_product_is_rejected() {
+        let beta = F::from_canonical_u64(7);
+        let gamma = F::from_canonical_u64(11);
+        let local = [FE::from_canonical_u64(3), FE::from_canonical_u64(5)];
+        let z_local = FE::from_canonical_u64(2);
+        let z_next = FE::from_canonical_u64(999); // does not satisfy the recurrence
+
+        assert_ne!(
+            transition_term(local, z_local, z_next, beta, gamma),
+            FE::ZERO
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! The STARK proof format: what the prover commits to, what it opens at `zeta`/`g*zeta`, and the
+//! challenges the verifier derives from it. Each native type has a `*Target` mirror used by the
+//! recursive verifier.
+
+use anyhow::Result;
+use plonky2::field::extension_field::target::ExtensionTarget;
+use plonky2::field::extension_field::Extendable;
+use plonky2::fri::proof::{FriChallenges, FriChallengesTarget, FriOpenings, FriProof, FriProofTarget};
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::iop::challenger::{Challenger, RecursiveChallenger};
+use plonky2::iop::target::Target;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2_util::log2_strict;
+
+use crate::config::StarkConfig;
+use crate::permutation::{get_n_permutation_challenge_sets, PermutationChallengeSet};
+
+/// Everything the prover commits to and opens for one STARK table.
+#[derive(Debug, Clone)]
+pub struct StarkProof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    /// Merkle cap of the fixed/preprocessed columns, if the `Stark` uses any. This is part of the
+    /// verification key rather than a per-proof commitment, but is threaded through here so the
+    /// verifier can check the prover didn't swap it out.
+    pub preprocessed_cap: Option<MerkleCap<F, C::Hasher>>,
+    pub trace_cap: MerkleCap<F, C::Hasher>,
+    /// Merkle cap of the permutation argument's running-product polynomials, if any.
+    pub permutation_zs_cap: Option<MerkleCap<F, C::Hasher>>,
+    pub quotient_polys_cap: MerkleCap<F, C::Hasher>,
+    pub openings: StarkOpeningSet<F, D>,
+    pub opening_proof: FriProof<F, C::Hasher, D>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> StarkProof<F, C, D> {
+    /// Reconstruct `degree_bits` from the FRI query structure alone, the way the verifier does
+    /// before trusting anything else in the proof.
+    pub fn recover_degree_bits(&self, config: &StarkConfig) -> usize {
+        let proof = &self.opening_proof;
+        log2_strict(
+            1 << (proof.query_round_proofs[0]
+                .initial_trees_proof
+                .evals_proofs[0]
+                .1
+                .siblings
+                .len()
+                + config.fri_config.cap_height
+                - config.fri_config.rate_bits),
+        )
+    }
+
+    /// Derive this table's challenges from a challenger that has already observed whatever the
+    /// caller wants bound into the transcript ahead of the proof itself (e.g. public inputs).
+    pub fn get_challenges<H: plonky2::hash::hashing::PlonkyPermutation>(
+        &self,
+        challenger: &mut Challenger<F, H>,
+        config: &StarkConfig,
+        degree_bits: usize,
+        num_permutation_zs: usize,
+    ) -> StarkProofChallenges<F, D> {
+        let StarkProof {
+            preprocessed_cap,
+            trace_cap,
+            permutation_zs_cap,
+            quotient_polys_cap,
+            openings,
+            opening_proof,
+        } = self;
+
+        if let Some(cap) = preprocessed_cap {
+            challenger.observe_cap(cap);
+        }
+        challenger.observe_cap(trace_cap);
+
+        let permutation_challenge_sets = if num_permutation_zs > 0 {
+            Some(get_n_permutation_challenge_sets(
+                challenger,
+                config.num_challenges,
+                num_permutation_zs,
+            ))
+        } else {
+            None
+        };
+        if let Some(cap) = permutation_zs_cap {
+            challenger.observe_cap(cap);
+        }
+
+        let stark_alphas = challenger.get_n_challenges(config.num_challenges);
+
+        challenger.observe_cap(quotient_polys_cap);
+        let stark_zeta = challenger.get_extension_challenge::<D>();
+
+        challenger.observe_openings(&openings.to_fri_openings());
+
+        StarkProofChallenges {
+            permutation_challenges: permutation_challenge_sets,
+            stark_alphas,
+            stark_zeta,
+            fri_challenges: challenger.fri_challenges::<C, D>(
+                &opening_proof.commit_phase_merkle_caps,
+                &opening_proof.final_poly,
+                opening_proof.pow_witness,
+                degree_bits,
+                &config.fri_config,
+            ),
+        }
+    }
+}
+
+/// The values a STARK's trace polynomials (and any auxiliary polynomials) are opened to, at
+/// `zeta` and, for polynomials with a transition constraint, at `g * zeta`.
+#[derive(Debug, Clone)]
+pub struct StarkOpeningSet<F: RichField + Extendable<D>, const D: usize> {
+    pub local_values: Vec<F::Extension>,
+    pub next_values: Vec<F::Extension>,
+    pub preprocessed_values: Vec<F::Extension>,
+    pub permutation_zs: Vec<F::Extension>,
+    pub permutation_zs_next: Vec<F::Extension>,
+    pub quotient_polys: Vec<F::Extension>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> StarkOpeningSet<F, D> {
+    pub fn to_fri_openings(&self) -> FriOpenings<F, D> {
+        let zeta_batch = self
+            .local_values
+            .iter()
+            .chain(&self.preprocessed_values)
+            .chain(&self.permutation_zs)
+            .chain(&self.quotient_polys)
+            .copied()
+            .collect();
+        let zeta_next_batch = self
+            .next_values
+            .iter()
+            .chain(&self.permutation_zs_next)
+            .copied()
+            .collect();
+        FriOpenings {
+            batches: vec![
+                plonky2::fri::proof::FriOpeningBatch { values: zeta_batch },
+                plonky2::fri::proof::FriOpeningBatch {
+                    values: zeta_next_batch,
+                },
+            ],
+        }
+    }
+}
+
+/// The Fiat-Shamir challenges squeezed while verifying one [`StarkProof`].
+#[derive(Debug, Clone)]
+pub struct StarkProofChallenges<F: RichField + Extendable<D>, const D: usize> {
+    /// One `(beta, gamma)` set per permutation-argument batch, if the `Stark` uses the argument.
+    pub permutation_challenges: Option<Vec<PermutationChallengeSet<F>>>,
+    pub stark_alphas: Vec<F>,
+    pub stark_zeta: F::Extension,
+    pub fri_challenges: FriChallenges<F, D>,
+}
+
+/// A [`StarkProof`] together with the public inputs it was produced for.
+#[derive(Debug, Clone)]
+pub struct StarkProofWithPublicInputs<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    pub proof: StarkProof<F, C, D>,
+    pub public_inputs: Vec<F>,
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    StarkProofWithPublicInputs<F, C, D>
+{
+    /// Derive challenges without binding `public_inputs` into the transcript first. Kept for
+    /// callers that manage the Fiat-Shamir transcript on their own; `verifier::verify_stark_proof`
+    /// does *not* use this, since it must bind public inputs before squeezing any challenge, and
+    /// neither does the recursive verifier, which uses
+    /// `StarkProofWithPublicInputsTarget::get_challenges`.
+    pub fn get_challenges(
+        &self,
+        config: &StarkConfig,
+        degree_bits: usize,
+        num_permutation_zs: usize,
+    ) -> Result<StarkProofChallenges<F, D>> {
+        let mut challenger = Challenger::<F, C::Hasher>::new();
+        Ok(self
+            .proof
+            .get_challenges(&mut challenger, config, degree_bits, num_permutation_zs))
+    }
+}
+
+// ------------------------------------------------------------------------------------------
+// In-circuit mirrors.
+// ------------------------------------------------------------------------------------------
+
+#[derive(Clone)]
+pub struct StarkProofTarget<const D: usize> {
+    pub preprocessed_cap: Option<plonky2::hash::merkle_tree::MerkleCapTarget>,
+    pub trace_cap: plonky2::hash::merkle_tree::MerkleCapTarget,
+    pub permutation_zs_cap: Option<plonky2::hash::merkle_tree::MerkleCapTarget>,
+    pub quotient_polys_cap: plonky2::hash::merkle_tree::MerkleCapTarget,
+    pub openings: StarkOpeningSetTarget<D>,
+    pub opening_proof: FriProofTarget<D>,
+}
+
+impl<const D: usize> StarkProofTarget<D> {
+    pub fn recover_degree_bits(&self, config: &StarkConfig) -> usize {
+        log2_strict(
+            1 << (self.opening_proof.query_round_proofs[0]
+                .initial_trees_proof
+                .evals_proofs[0]
+                .1
+                .siblings
+                .len()
+                + config.fri_config.cap_height
+                - config.fri_config.rate_bits),
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct StarkOpeningSetTarget<const D: usize> {
+    pub local_values: Vec<ExtensionTarget<D>>,
+    pub next_values: Vec<ExtensionTarget<D>>,
+    pub preprocessed_values: Vec<ExtensionTarget<D>>,
+    pub permutation_zs: Vec<ExtensionTarget<D>>,
+    pub permutation_zs_next: Vec<ExtensionTarget<D>>,
+    pub quotient_polys: Vec<ExtensionTarget<D>>,
+}
+
+impl<const D: usize> StarkOpeningSetTarget<D> {
+    pub fn to_fri_openings(&self) -> plonky2::fri::proof::FriOpeningsTarget<D> {
+        let zeta_batch = self
+            .local_values
+            .iter()
+            .chain(&self.preprocessed_values)
+            .chain(&self.permutation_zs)
+            .chain(&self.quotient_polys)
+            .copied()
+            .collect();
+        let zeta_next_batch = self
+            .next_values
+            .iter()
+            .chain(&self.permutation_zs_next)
+            .copied()
+            .collect();
+        plonky2::fri::proof::FriOpeningsTarget {
+            batches: vec![
+                plonky2::fri::proof::FriOpeningBatchTarget { values: zeta_batch },
+                plonky2::fri::proof::FriOpeningBatchTarget {
+                    values: zeta_next_batch,
+                },
+            ],
+        }
+    }
+}
+
+pub struct StarkProofChallengesTarget<const D: usize> {
+    pub permutation_challenges: Option<Vec<crate::permutation::PermutationChallengeSetTarget>>,
+    pub stark_alphas: Vec<Target>,
+    pub stark_zeta: ExtensionTarget<D>,
+    pub fri_challenges: FriChallengesTarget<D>,
+}
+
+#[derive(Clone)]
+pub struct StarkProofWithPublicInputsTarget<const D: usize> {
+    pub proof: StarkProofTarget<D>,
+    pub public_inputs: Vec<Target>,
+}
+
+impl<const D: usize> StarkProofWithPublicInputsTarget<D> {
+    /// Derive challenges inside the circuit, binding `public_inputs` into the transcript first
+    /// (matching `verifier::get_challenges_binding_public_inputs`'s native soundness fix): the
+    /// in-circuit and native transcripts must absorb everything in the identical order, since the
+    /// whole point of this verifier is to reproduce the native one.
+    pub fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        config: &StarkConfig,
+        degree_bits: usize,
+        num_permutation_zs: usize,
+    ) -> StarkProofChallengesTarget<D>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let mut challenger = RecursiveChallenger::<F, C::Hasher, D>::new(builder);
+        challenger.observe_elements(&self.public_inputs);
+        if let Some(cap) = &self.proof.preprocessed_cap {
+            challenger.observe_cap(cap);
+        }
+        challenger.observe_cap(&self.proof.trace_cap);
+
+        let permutation_challenges = if num_permutation_zs > 0 {
+            Some(
+                (0..num_permutation_zs)
+                    .map(|_| crate::permutation::PermutationChallengeSetTarget {
+                        challenges: (0..config.num_challenges)
+                            .map(|_| crate::permutation::PermutationChallengeTarget {
+                                beta: challenger.get_challenge(builder),
+                                gamma: challenger.get_challenge(builder),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        if let Some(cap) = &self.proof.permutation_zs_cap {
+            challenger.observe_cap(cap);
+        }
+
+        let stark_alphas = challenger.get_n_challenges(builder, config.num_challenges);
+
+        challenger.observe_cap(&self.proof.quotient_polys_cap);
+        let stark_zeta = challenger.get_extension_challenge(builder);
+
+        challenger.observe_openings(&self.proof.openings.to_fri_openings());
+
+        let fri_challenges = challenger.fri_challenges::<C>(
+            builder,
+            &self.proof.opening_proof.commit_phase_merkle_caps,
+            &self.proof.opening_proof.final_poly,
+            self.proof.opening_proof.pow_witness,
+            degree_bits,
+            &config.fri_config,
+        );
+
+        StarkProofChallengesTarget {
+            permutation_challenges,
+            stark_alphas,
+            stark_zeta,
+            fri_challenges,
+        }
+    }
+}
+
@@ -0,0 +1,279 @@
+//! Recursive verification of STARK proofs inside a plonky2 circuit.
+
+use plonky2::field::extension_field::target::ExtensionTarget;
+use plonky2::field::extension_field::Extendable;
+use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCapTarget;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::config::{AlgebraicHasher, GenericConfig};
+use plonky2::plonk::plonk_common::reduce_with_powers_ext_circuit;
+use plonky2::with_context;
+
+use crate::config::StarkConfig;
+use crate::constraint_consumer::RecursiveConstraintConsumer;
+use crate::permutation::{eval_permutation_checks_circuit, PermutationCheckVarsTarget};
+use crate::proof::{StarkProofChallengesTarget, StarkProofWithPublicInputsTarget};
+use crate::stark::Stark;
+use crate::vars::StarkEvaluationTargets;
+
+/// Recursively verify a STARK proof produced with `config` inside `builder`.
+///
+/// This mirrors [`crate::verifier::verify_with_challenges`], but every field element becomes a
+/// `Target`/`ExtensionTarget` and every check becomes an in-circuit assertion instead of a
+/// native one.
+pub fn verify_stark_proof_circuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: S,
+    proof_with_pis: StarkProofWithPublicInputsTarget<D>,
+    preprocessed_cap: Option<&MerkleCapTarget>,
+    config: &StarkConfig,
+) where
+    C::Hasher: AlgebraicHasher<F>,
+    [(); S::COLUMNS]:,
+    [(); S::PUBLIC_INPUTS]:,
+{
+    assert_preprocessed_cap_matches_circuit(
+        builder,
+        preprocessed_cap,
+        &proof_with_pis.proof.preprocessed_cap,
+    );
+
+    let degree_bits = proof_with_pis.proof.recover_degree_bits(config);
+    let num_permutation_zs = stark.num_permutation_zs(config);
+    let challenges = with_context!(
+        builder,
+        "compute challenges",
+        proof_with_pis.get_challenges::<F, C>(builder, config, degree_bits, num_permutation_zs)
+    );
+    verify_stark_proof_with_challenges_circuit::<F, C, S, D>(
+        builder,
+        stark,
+        proof_with_pis,
+        challenges,
+        config,
+        degree_bits,
+    );
+}
+
+fn verify_stark_proof_with_challenges_circuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    builder: &mut CircuitBuilder<F, D>,
+    stark: S,
+    proof_with_pis: StarkProofWithPublicInputsTarget<D>,
+    challenges: StarkProofChallengesTarget<D>,
+    config: &StarkConfig,
+    degree_bits: usize,
+) where
+    C::Hasher: AlgebraicHasher<F>,
+    [(); S::COLUMNS]:,
+    [(); S::PUBLIC_INPUTS]:,
+{
+    let one = builder.one_extension();
+
+    let StarkProofWithPublicInputsTarget {
+        proof,
+        public_inputs,
+    } = proof_with_pis;
+    let openings = &proof.openings;
+
+    let vars = StarkEvaluationTargets {
+        local_values: &openings.local_values.to_vec().try_into().unwrap(),
+        next_values: &openings.next_values.to_vec().try_into().unwrap(),
+        preprocessed_values: &openings.preprocessed_values,
+        public_inputs: &public_inputs.to_vec().try_into().unwrap(),
+    };
+
+    let zeta_pow_deg = builder.exp_power_of_2_extension(challenges.stark_zeta, degree_bits);
+    let z_h_zeta = builder.sub_extension(zeta_pow_deg, one);
+    let (l_1, l_last) =
+        eval_l_1_and_l_last_circuit(builder, degree_bits, challenges.stark_zeta, z_h_zeta);
+
+    let last =
+        builder.constant_extension(F::Extension::from_basefield(
+            F::primitive_root_of_unity(degree_bits).inverse(),
+        ));
+    let z_last = builder.sub_extension(challenges.stark_zeta, last);
+
+    let mut consumer = RecursiveConstraintConsumer::<F, D>::new(
+        builder.zero_extension(),
+        challenges.stark_alphas.clone(),
+        l_1,
+        l_last,
+    );
+
+    with_context!(
+        builder,
+        "evaluate AIR constraints",
+        stark.eval_ext_circuit(builder, vars, &mut consumer)
+    );
+
+    if stark.uses_permutation_args() {
+        let permutation_challenge_sets = challenges
+            .permutation_challenges
+            .clone()
+            .expect("Permutation challenges not found");
+        let permutation_vars = PermutationCheckVarsTarget {
+            local_zs: openings.permutation_zs.clone(),
+            next_zs: openings.permutation_zs_next.clone(),
+            permutation_challenge_sets,
+        };
+        with_context!(
+            builder,
+            "evaluate permutation-argument constraints",
+            eval_permutation_checks_circuit::<F, S, D>(
+                builder,
+                &stark,
+                config,
+                vars,
+                permutation_vars,
+                &mut consumer,
+            )
+        );
+    }
+
+    let vanishing_polys_zeta = consumer.accumulators();
+
+    // Reconstruct `t(zeta)` from its chunks: `t(X) = t_0(X) + t_1(X) X^n + ...`.
+    let quotient_polys_zeta = &openings.quotient_polys;
+    for (i, chunk) in quotient_polys_zeta
+        .chunks(1 << config.fri_config.rate_bits)
+        .enumerate()
+    {
+        let recombined_quotient =
+            reduce_with_powers_ext_circuit(builder, chunk, zeta_pow_deg);
+        let composite_eval = builder.mul_extension(z_h_zeta, recombined_quotient);
+        let lhs = builder.mul_extension(vanishing_polys_zeta[i], z_last);
+        builder.connect_extension(lhs, composite_eval);
+    }
+
+    // Mirror the native `verify_with_challenges`'s `merkle_caps` construction exactly: the FRI
+    // opening batch includes `preprocessed_values`/`permutation_zs` whenever those caps are
+    // present, so the oracle list passed to FRI verification must include them in the same order
+    // or the in-circuit verifier checks openings against the wrong commitment.
+    let mut merkle_caps = vec![];
+    if let Some(cap) = &proof.preprocessed_cap {
+        merkle_caps.push(cap.clone());
+    }
+    merkle_caps.push(proof.trace_cap.clone());
+    if let Some(cap) = &proof.permutation_zs_cap {
+        merkle_caps.push(cap.clone());
+    }
+    merkle_caps.push(proof.quotient_polys_cap.clone());
+
+    let fri_instance = S::fri_instance_target(
+        builder,
+        challenges.stark_zeta,
+        F::primitive_root_of_unity(degree_bits),
+        config.fri_config.rate_bits,
+        config.num_challenges,
+    );
+    builder.verify_fri_proof_circuit::<C>(
+        &fri_instance,
+        &openings.to_fri_openings(),
+        &challenges.fri_challenges,
+        &merkle_caps,
+        &proof.opening_proof,
+        &config.fri_params(degree_bits),
+    );
+}
+
+/// Circuit version of `eval_l_1_and_l_last`: evaluate the Lagrange basis polynomials `L_1` and
+/// `L_n` at `zeta`, given `Z_H(zeta) = zeta^n - 1`.
+fn eval_l_1_and_l_last_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    log_n: usize,
+    zeta: ExtensionTarget<D>,
+    z_h_zeta: ExtensionTarget<D>,
+) -> (ExtensionTarget<D>, ExtensionTarget<D>) {
+    let n = builder.constant_extension(F::Extension::from_canonical_usize(1 << log_n));
+    let g = F::primitive_root_of_unity(log_n);
+
+    let one = builder.one_extension();
+    let zeta_minus_one = builder.sub_extension(zeta, one);
+    let denominator_1 = builder.mul_extension(n, zeta_minus_one);
+    let l_1 = builder.div_extension(z_h_zeta, denominator_1);
+
+    let g_zeta = builder.mul_const_extension(g, zeta);
+    let g_zeta_minus_one = builder.sub_extension(g_zeta, one);
+    let denominator_last = builder.mul_extension(n, g_zeta_minus_one);
+    let l_last = builder.div_extension(z_h_zeta, denominator_last);
+
+    (l_1, l_last)
+}
+
+/// Assert in-circuit that `actual` equals `expected`, or that both are absent. The preprocessed
+/// cap is part of the verification key rather than the proof, so a presence mismatch is a
+/// circuit-shape error (caught immediately, not a witness-dependent constraint) while a value
+/// mismatch is wired in as a copy constraint the prover's witness must satisfy.
+fn assert_preprocessed_cap_matches_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    expected: Option<&MerkleCapTarget>,
+    actual: &Option<MerkleCapTarget>,
+) {
+    match (expected, actual) {
+        (Some(expected), Some(actual)) => {
+            for (&a, &b) in expected.0.iter().zip(&actual.0) {
+                builder.connect_hashes(a, b);
+            }
+        }
+        (None, None) => {}
+        _ => panic!("preprocessed cap presence does not match the verification key"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::goldilocks_field::GoldilocksField as F;
+    use plonky2::hash::hash_types::HashOut;
+    use plonky2::iop::witness::{PartialWitness, Witness};
+    use plonky2::plonk::circuit_data::CircuitConfig;
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+
+    use super::*;
+
+    type C = PoseidonGoldilocksConfig;
+    const D: usize = 2;
+
+    fn virtual_cap(builder: &mut CircuitBuilder<F, D>, height: usize) -> MerkleCapTarget {
+        MerkleCapTarget((0..1 << height).map(|_| builder.add_virtual_hash()).collect())
+    }
+
+    #[test]
+    fn equal_caps_are_accepted_and_provable() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let expected = virtual_cap(&mut builder, 1);
+        let actual = virtual_cap(&mut builder, 1);
+        assert_preprocessed_cap_matches_circuit(&mut builder, Some(&expected), &Some(actual.clone()));
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        let hash = HashOut {
+            elements: [F::from_canonical_u64(5); 4],
+        };
+        for (&e, &a) in expected.0.iter().zip(&actual.0) {
+            pw.set_hash_target(e, hash);
+            pw.set_hash_target(a, hash);
+        }
+        let proof = data.prove(pw).unwrap();
+        assert!(data.verify(proof).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "preprocessed cap presence does not match the verification key")]
+    fn presence_mismatch_panics_at_circuit_build_time() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let expected = virtual_cap(&mut builder, 1);
+        assert_preprocessed_cap_matches_circuit(&mut builder, Some(&expected), &None);
+    }
+}
@@ -0,0 +1,110 @@
+//! The `Stark` trait: an AIR's constraints, plus the bits of metadata the native and recursive
+//! verifiers need to plug those constraints into the quotient/FRI machinery (column counts,
+//! permutation-argument pairing, and the FRI instance description).
+
+use plonky2::field::extension_field::target::ExtensionTarget;
+use plonky2::field::extension_field::{Extendable, FieldExtension};
+use plonky2::field::packed_field::PackedField;
+use plonky2::fri::structure::{FriInstanceInfo, FriInstanceInfoTarget};
+use plonky2::hash::hash_types::RichField;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+
+use crate::config::StarkConfig;
+use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+use crate::permutation::PermutationPair;
+use crate::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+
+/// A STARK: a set of constraints an execution trace must satisfy, described once and evaluated
+/// generically over plain field elements (for the prover), packed fields (for fast native
+/// verification), and in-circuit targets (for the recursive verifier).
+pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
+    /// The number of trace columns.
+    const COLUMNS: usize;
+    /// The number of public inputs.
+    const PUBLIC_INPUTS: usize;
+
+    /// Evaluate the AIR constraints generically, for any `P` packing `FE`-valued evaluations of an
+    /// `F`-extension of degree `D2`. Used for both the (`D2`=1) packed native trace evaluation and
+    /// the (`D2`=`D`) evaluation at `zeta` during verification.
+    fn eval_packed_generic<FE, P, const D2: usize>(
+        &self,
+        vars: StarkEvaluationVars<FE, P, { Self::COLUMNS }, { Self::PUBLIC_INPUTS }>,
+        consumer: &mut ConstraintConsumer<P>,
+    ) where
+        FE: FieldExtension<D2, BaseField = F>,
+        P: PackedField<Scalar = FE>;
+
+    /// Evaluate the AIR constraints at an extension-field point (i.e. `zeta`).
+    fn eval_ext(
+        &self,
+        vars: StarkEvaluationVars<F::Extension, F::Extension, { Self::COLUMNS }, { Self::PUBLIC_INPUTS }>,
+        consumer: &mut ConstraintConsumer<F::Extension>,
+    ) {
+        self.eval_packed_generic(vars, consumer)
+    }
+
+    /// In-circuit mirror of [`Stark::eval_ext`].
+    fn eval_ext_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: StarkEvaluationTargets<D, { Self::COLUMNS }, { Self::PUBLIC_INPUTS }>,
+        consumer: &mut RecursiveConstraintConsumer<F, D>,
+    );
+
+    /// The maximum degree, over `X`, of any single constraint (as a polynomial in the trace
+    /// columns). Determines how many chunks the quotient polynomial is split into.
+    fn constraint_degree(&self) -> usize;
+
+    fn quotient_degree_factor(&self) -> usize {
+        1.max(self.constraint_degree() - 1)
+    }
+
+    /// Column pairs that should be permutations of one another (the permutation/lookup argument).
+    /// Empty for STARKs that don't use it.
+    fn permutation_pairs(&self) -> Vec<PermutationPair> {
+        vec![]
+    }
+
+    fn uses_permutation_args(&self) -> bool {
+        !self.permutation_pairs().is_empty()
+    }
+
+    /// How many `PermutationPair`s share a single running-product `Z` polynomial. Bounded by the
+    /// constraint degree, since each pair in a batch contributes a factor to the same transition
+    /// constraint.
+    fn permutation_batch_size(&self) -> usize {
+        // A batch of `k` pairs gives a transition constraint of degree `k + 1` (the extra factor
+        // is `Z(g x)`), so the batch is one smaller than the constraint degree, and at least 1.
+        self.quotient_degree_factor()
+    }
+
+    /// The number of permutation-argument `Z` polynomials the prover commits to, given
+    /// `permutation_pairs` batched `permutation_batch_size` at a time.
+    fn num_permutation_zs(&self, _config: &StarkConfig) -> usize {
+        let pairs = self.permutation_pairs().len();
+        if pairs == 0 {
+            0
+        } else {
+            (pairs + self.permutation_batch_size() - 1) / self.permutation_batch_size()
+        }
+    }
+
+    /// Describe, for the FRI verifier, which oracles this `Stark` commits to and what they should
+    /// be opened at. Takes no `self`: it depends only on `Self`'s column/public-input counts and
+    /// permutation pairing, which are determined by the type, not by any particular value.
+    fn fri_instance(
+        zeta: F::Extension,
+        g: F,
+        rate_bits: usize,
+        num_challenges: usize,
+    ) -> FriInstanceInfo<F, D>;
+
+    /// In-circuit mirror of [`Stark::fri_instance`].
+    fn fri_instance_target(
+        builder: &mut CircuitBuilder<F, D>,
+        zeta: ExtensionTarget<D>,
+        g: F,
+        rate_bits: usize,
+        num_challenges: usize,
+    ) -> FriInstanceInfoTarget<D>;
+}
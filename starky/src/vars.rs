@@ -0,0 +1,29 @@
+//! The trace values a `Stark`'s constraints are evaluated against: the current row, the next
+//! row, any preprocessed/fixed columns, and the public inputs. Native evaluation instantiates `P`
+//! with a packed or extension field; [`StarkEvaluationTargets`] is the in-circuit mirror.
+
+use plonky2::field::extension_field::target::ExtensionTarget;
+use plonky2::field::packed_field::PackedField;
+use plonky2::iop::target::Target;
+
+#[derive(Debug, Copy, Clone)]
+pub struct StarkEvaluationVars<'a, F, P, const COLUMNS: usize, const PUBLIC_INPUTS: usize>
+where
+    F: Copy,
+    P: PackedField<Scalar = F>,
+{
+    pub local_values: &'a [P; COLUMNS],
+    pub next_values: &'a [P; COLUMNS],
+    /// Fixed/preprocessed columns (round constants, selectors, ...), opened at the same point as
+    /// `local_values`. Empty for STARKs that don't use any.
+    pub preprocessed_values: &'a [P],
+    pub public_inputs: &'a [P; PUBLIC_INPUTS],
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct StarkEvaluationTargets<'a, const D: usize, const COLUMNS: usize, const PUBLIC_INPUTS: usize> {
+    pub local_values: &'a [ExtensionTarget<D>; COLUMNS],
+    pub next_values: &'a [ExtensionTarget<D>; COLUMNS],
+    pub preprocessed_values: &'a [ExtensionTarget<D>],
+    pub public_inputs: &'a [Target; PUBLIC_INPUTS],
+}
@@ -3,6 +3,8 @@ use plonky2::field::extension_field::{Extendable, FieldExtension};
 use plonky2::field::field_types::Field;
 use plonky2::fri::verifier::verify_fri_proof;
 use plonky2::hash::hash_types::RichField;
+use plonky2::hash::merkle_tree::MerkleCap;
+use plonky2::iop::challenger::Challenger;
 use plonky2::plonk::circuit_data::CommonCircuitData;
 use plonky2::plonk::config::GenericConfig;
 use plonky2::plonk::plonk_common::reduce_with_powers;
@@ -11,11 +13,18 @@ use plonky2_util::log2_strict;
 
 use crate::config::StarkConfig;
 use crate::constraint_consumer::ConstraintConsumer;
+use crate::permutation::{eval_permutation_checks, PermutationCheckVars};
 use crate::proof::{StarkOpeningSet, StarkProof, StarkProofChallenges, StarkProofWithPublicInputs};
 use crate::stark::Stark;
 use crate::vars::StarkEvaluationVars;
 
-pub(crate) fn verify<
+/// Verify a STARK proof, binding `public_inputs` into the transcript before any challenge is
+/// derived. `degree_bits` is recovered from the Merkle path length of the proof's first FRI query
+/// round rather than supplied by the caller; it is not compared with any other length claim.
+///
+/// This is the entry point external callers should use; [`verify_with_challenges`] stays
+/// `pub(crate)` since it takes already-derived, already-trusted challenges.
+pub fn verify_stark_proof<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
     S: Stark<F, D>,
@@ -24,14 +33,51 @@ pub(crate) fn verify<
     stark: S,
     proof_with_pis: StarkProofWithPublicInputs<F, C, D>,
     config: &StarkConfig,
-    degree_bits: usize,
+    preprocessed_cap: Option<&MerkleCap<F, C::Hasher>>,
 ) -> Result<()>
 where
     [(); S::COLUMNS]:,
     [(); S::PUBLIC_INPUTS]:,
 {
-    let challenges = proof_with_pis.get_challenges(config, degree_bits)?;
-    verify_with_challenges(stark, proof_with_pis, challenges, config)
+    let degree_bits = log2_strict(recover_degree(&proof_with_pis.proof, config));
+
+    let challenges =
+        get_challenges_binding_public_inputs(&stark, &proof_with_pis, config, degree_bits);
+    verify_with_challenges(stark, proof_with_pis, challenges, config, preprocessed_cap)
+}
+
+/// Derive `StarkProofChallenges` the way `StarkProof::get_challenges` does, but observe
+/// `public_inputs` *before* any cap or challenge, so that a proof for a different public input is
+/// bound to fail: every downstream challenge, and hence the whole FRI transcript, changes with it.
+///
+/// `public_inputs` must be the very first thing observed, ahead of `preprocessed_cap`/`trace_cap`,
+/// which `proof.get_challenges` absorbs itself; absorbing either cap here too would double-absorb
+/// it and desync this transcript from every other caller of `proof.get_challenges`.
+fn get_challenges_binding_public_inputs<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    S: Stark<F, D>,
+    const D: usize,
+>(
+    stark: &S,
+    proof_with_pis: &StarkProofWithPublicInputs<F, C, D>,
+    config: &StarkConfig,
+    degree_bits: usize,
+) -> StarkProofChallenges<F, D> {
+    let StarkProofWithPublicInputs {
+        proof,
+        public_inputs,
+    } = proof_with_pis;
+
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    challenger.observe_elements(public_inputs);
+
+    proof.get_challenges(
+        &mut challenger,
+        config,
+        degree_bits,
+        stark.num_permutation_zs(config),
+    )
 }
 
 pub(crate) fn verify_with_challenges<
@@ -44,6 +90,7 @@ pub(crate) fn verify_with_challenges<
     proof_with_pis: StarkProofWithPublicInputs<F, C, D>,
     challenges: StarkProofChallenges<F, D>,
     config: &StarkConfig,
+    preprocessed_cap: Option<&MerkleCap<F, C::Hasher>>,
 ) -> Result<()>
 where
     [(); S::COLUMNS]:,
@@ -56,17 +103,32 @@ where
     let degree = recover_degree(&proof, config);
     let degree_bits = log2_strict(degree);
 
+    // The preprocessed cap is part of the verification key, not the proof: check the prover sent
+    // the same constant-column commitment we were configured with before trusting any openings
+    // against it.
+    match (preprocessed_cap, &proof.preprocessed_cap) {
+        (Some(expected), Some(actual)) => ensure!(
+            expected == actual,
+            "preprocessed Merkle cap does not match the verification key"
+        ),
+        (None, None) => {}
+        _ => anyhow::bail!("preprocessed cap presence does not match the verification key"),
+    }
+
     let local_values = &proof.openings.local_values;
     let next_values = &proof.openings.local_values;
     let StarkOpeningSet {
         local_values,
         next_values,
+        preprocessed_values,
         permutation_zs,
+        permutation_zs_next,
         quotient_polys,
     } = &proof.openings;
     let vars = StarkEvaluationVars {
         local_values: &local_values.to_vec().try_into().unwrap(),
         next_values: &next_values.to_vec().try_into().unwrap(),
+        preprocessed_values: &preprocessed_values.to_vec(),
         public_inputs: &public_inputs
             .into_iter()
             .map(F::Extension::from_basefield)
@@ -86,6 +148,26 @@ where
         l_last.into(),
     );
     stark.eval_ext(vars, &mut consumer);
+
+    if stark.uses_permutation_args() {
+        let permutation_challenge_sets = challenges
+            .permutation_challenges
+            .as_ref()
+            .expect("Permutation challenges not found");
+        let permutation_vars = PermutationCheckVars {
+            local_zs: permutation_zs.clone(),
+            next_zs: permutation_zs_next.clone(),
+            permutation_challenge_sets: permutation_challenge_sets.clone(),
+        };
+        eval_permutation_checks::<F, F::Extension, F::Extension, S, D, D>(
+            &stark,
+            config,
+            vars,
+            permutation_vars,
+            &mut consumer,
+        );
+    }
+
     let acc = consumer.accumulators();
 
     // Check each polynomial identity, of the form `vanishing(x) = Z_H(x) quotient(x)`, at zeta.
@@ -107,7 +189,15 @@ where
         ensure!(acc[i] == z_h_zeta * reduce_with_powers(chunk, zeta_pow_deg) / z_last);
     }
 
-    let merkle_caps = &[proof.trace_cap, proof.quotient_polys_cap];
+    let mut merkle_caps = vec![];
+    if let Some(cap) = &proof.preprocessed_cap {
+        merkle_caps.push(cap.clone());
+    }
+    merkle_caps.push(proof.trace_cap);
+    if let Some(cap) = &proof.permutation_zs_cap {
+        merkle_caps.push(cap.clone());
+    }
+    merkle_caps.push(proof.quotient_polys_cap);
 
     verify_fri_proof::<F, C, D>(
         &S::fri_instance(
@@ -118,7 +208,7 @@ where
         ),
         &proof.openings.to_fri_openings(),
         &challenges.fri_challenges,
-        merkle_caps,
+        &merkle_caps,
         &proof.opening_proof,
         &config.fri_params(degree_bits),
     )?;
@@ -152,3 +242,146 @@ fn recover_degree<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, cons
         + config.fri_config.cap_height
         - config.fri_config.rate_bits)
 }
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::extension_field::target::ExtensionTarget;
+    use plonky2::field::field_types::Field;
+    use plonky2::field::goldilocks_field::GoldilocksField as F;
+    use plonky2::field::polynomial::PolynomialCoeffs;
+    use plonky2::fri::proof::FriProof;
+    use plonky2::fri::structure::{FriInstanceInfo, FriInstanceInfoTarget};
+    use plonky2::plonk::config::{GenericConfig, Hasher, PoseidonGoldilocksConfig};
+
+    use super::*;
+    use crate::constraint_consumer::{ConstraintConsumer, RecursiveConstraintConsumer};
+    use crate::vars::{StarkEvaluationTargets, StarkEvaluationVars};
+
+    type C = PoseidonGoldilocksConfig;
+    const D: usize = 2;
+
+    /// A `Stark` with no constraints and no permutation argument: just enough to drive
+    /// `get_challenges_binding_public_inputs` without any AIR-specific logic getting in the way.
+    struct NoOpStark;
+
+    impl Stark<F, D> for NoOpStark {
+        const COLUMNS: usize = 0;
+        const PUBLIC_INPUTS: usize = 2;
+
+        fn eval_packed_generic<FE, P, const D2: usize>(
+            &self,
+            _vars: StarkEvaluationVars<FE, P, { Self::COLUMNS }, { Self::PUBLIC_INPUTS }>,
+            _consumer: &mut ConstraintConsumer<P>,
+        ) where
+            FE: FieldExtension<D2, BaseField = F>,
+            P: plonky2::field::packed_field::PackedField<Scalar = FE>,
+        {
+        }
+
+        fn eval_ext_circuit(
+            &self,
+            _builder: &mut plonky2::plonk::circuit_builder::CircuitBuilder<F, D>,
+            _vars: StarkEvaluationTargets<D, { Self::COLUMNS }, { Self::PUBLIC_INPUTS }>,
+            _consumer: &mut RecursiveConstraintConsumer<F, D>,
+        ) {
+        }
+
+        fn constraint_degree(&self) -> usize {
+            2
+        }
+
+        fn fri_instance(
+            _zeta: F::Extension,
+            _g: F,
+            _rate_bits: usize,
+            _num_challenges: usize,
+        ) -> FriInstanceInfo<F, D> {
+            unimplemented!("not exercised by these tests")
+        }
+
+        fn fri_instance_target(
+            _builder: &mut plonky2::plonk::circuit_builder::CircuitBuilder<F, D>,
+            _zeta: ExtensionTarget<D>,
+            _g: F,
+            _rate_bits: usize,
+            _num_challenges: usize,
+        ) -> FriInstanceInfoTarget<D> {
+            unimplemented!("not exercised by these tests")
+        }
+    }
+
+    /// A minimal-but-real `StarkProofWithPublicInputs`: an empty FRI proof and a correctly-shaped
+    /// but all-zero trace cap, varying only in `public_inputs`.
+    fn dummy_proof(public_inputs: Vec<F>) -> StarkProofWithPublicInputs<F, C, D> {
+        let cap_height = 1;
+        let cap = || {
+            MerkleCap::<F, <C as GenericConfig<D>>::Hasher>(vec![
+                <<C as GenericConfig<D>>::Hasher as Hasher<F>>::Hash::default();
+                1 << cap_height
+            ])
+        };
+        StarkProofWithPublicInputs {
+            proof: StarkProof {
+                preprocessed_cap: None,
+                trace_cap: cap(),
+                permutation_zs_cap: None,
+                quotient_polys_cap: cap(),
+                openings: StarkOpeningSet {
+                    local_values: vec![],
+                    next_values: vec![],
+                    preprocessed_values: vec![],
+                    permutation_zs: vec![],
+                    permutation_zs_next: vec![],
+                    quotient_polys: vec![],
+                },
+                opening_proof: FriProof {
+                    commit_phase_merkle_caps: vec![],
+                    query_round_proofs: vec![],
+                    final_poly: PolynomialCoeffs::new(vec![]),
+                    pow_witness: F::ZERO,
+                },
+            },
+            public_inputs,
+        }
+    }
+
+    #[test]
+    fn different_public_inputs_yield_different_challenges() {
+        let config = StarkConfig::standard_fast_config();
+        let stark = NoOpStark;
+        let degree_bits = 3;
+
+        let proof_a = dummy_proof(vec![F::ONE, F::TWO]);
+        let proof_b = dummy_proof(vec![F::ONE, F::from_canonical_u64(3)]);
+
+        let challenges_a =
+            get_challenges_binding_public_inputs(&stark, &proof_a, &config, degree_bits);
+        let challenges_b =
+            get_challenges_binding_public_inputs(&stark, &proof_b, &config, degree_bits);
+
+        assert_ne!(challenges_a.stark_alphas, challenges_b.stark_alphas);
+    }
+
+    #[test]
+    fn public_inputs_are_bound_ahead_of_trace_cap_not_on_top_of_it() {
+        // Manually build the reference transcript a correct implementation must produce: public
+        // inputs first (this `Stark` has no preprocessed cap), then `trace_cap`, then the alphas
+        // squeeze -- exactly once each, matching `StarkProof::get_challenges`. Observing
+        // `trace_cap` here *and* again inside `get_challenges_binding_public_inputs` would desync
+        // these two transcripts and fail this assertion.
+        let config = StarkConfig::standard_fast_config();
+        let stark = NoOpStark;
+        let degree_bits = 3;
+        let proof_with_pis = dummy_proof(vec![F::ONE, F::TWO]);
+
+        let mut reference = Challenger::<F, <C as GenericConfig<D>>::Hasher>::new();
+        reference.observe_elements(&proof_with_pis.public_inputs);
+        reference.observe_cap(&proof_with_pis.proof.trace_cap);
+        let reference_alphas = reference.get_n_challenges(config.num_challenges);
+
+        let challenges =
+            get_challenges_binding_public_inputs(&stark, &proof_with_pis, &config, degree_bits);
+
+        assert_eq!(challenges.stark_alphas, reference_alphas);
+    }
+}